@@ -1,13 +1,16 @@
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
 use std::ops::Range;
 
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use tinyvec::TinyVec;
 
 const INLINE_CAPACITY: usize = 2;
 
 #[derive(Debug, Default)]
-pub struct RangeSet(TinyVec<[Range<u64>; INLINE_CAPACITY]>);
+pub struct RangeSet<T: Copy + Ord + Default = u64>(TinyVec<[Range<T>; INLINE_CAPACITY]>);
 
-impl Clone for RangeSet {
+impl<T: Copy + Ord + Default> Clone for RangeSet<T> {
     fn clone(&self) -> Self {
         if self.0.is_inline() || self.0.len() > INLINE_CAPACITY {
             return Self(self.0.clone());
@@ -18,7 +21,8 @@ impl Clone for RangeSet {
     }
 }
 
-impl RangeSet {
+#[allow(clippy::len_without_is_empty)]
+impl<T: Copy + Ord + Default> RangeSet<T> {
     pub fn new() -> Self {
         Default::default()
     }
@@ -27,25 +31,35 @@ impl RangeSet {
         self.0.len()
     }
 
-    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Range<u64>> + '_ {
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Range<T>> + '_ {
         self.0.iter().cloned()
     }
 
-    /// Check if the range set contains a certain number
-    pub fn contains(&self, num: u64) -> bool {
-        for el in self.0.iter() {
-            if el.start > num {
-                return false;
-            } else if el.contains(&num) {
-                return true;
+    /// Binary search the sorted, disjoint ranges for `num`.
+    ///
+    /// Returns `Ok(idx)` when `num` falls inside range `idx`, or
+    /// `Err(insert_pos)` with the index a range containing `num` would
+    /// need to be inserted at to keep the set sorted.
+    fn search(&self, num: T) -> Result<usize, usize> {
+        self.0.binary_search_by(|r| {
+            if r.start <= num && num < r.end {
+                Ordering::Equal
+            } else if num < r.start {
+                Ordering::Greater
+            } else {
+                Ordering::Less
             }
-        }
-        false
+        })
+    }
+
+    /// Check if the range set contains a certain number
+    pub fn contains(&self, num: T) -> bool {
+        self.search(num).is_ok()
     }
 
     /// Check if the range set is empty
     pub fn empty(&self) -> bool {
-        return self.0.is_empty();
+        self.0.is_empty()
     }
 
     /// Uses `shrink_to_fit` of underlying tiny vec
@@ -56,105 +70,72 @@ impl RangeSet {
 
     /// Insert a Range into the range set
     /// uses std::ops::Range
-    /// 
+    ///
     /// example:
-    /// 
+    ///
     /// use std::ops::Range;
-    /// 
+    ///
     /// let range = std::ops::RangeSet{start: 1, end: 5};
     /// let mut set = RangeSet::new();
     /// set.insert(range);
-    pub fn insert(&mut self, range: Range<u64>) {
+    pub fn insert(&mut self, range: Range<T>) {
         if range.is_empty() {
             return;
         }
-        let mut index = 0;
-        while index != self.0.len() {
-            let current = &mut self.0[index];
-            if current.start > range.end {
-                self.0.insert(index, range);
-                return;
-            } else if current.start > range.start {
-                current.start = range.start;
-                return;
-            }
-            if range.end <= current.end {
-                return;
-            } else if range.start <= current.end {
-                current.end = range.end;
-                while index != self.0.len() - 1 {
-                    let curr = self.0[index].clone();
-                    let next = self.0[index + 1].clone();
-                    if curr.end >= next.start {
-                        self.0[index].end = next.end.max(curr.end);
-                        self.0.remove(index + 1);
-                    } else {
-                        break;
-                    }
-                }
-                return;
-            }
-            index += 1;
+
+        let mut index = match self.search(range.start) {
+            Ok(idx) => idx,
+            Err(idx) => idx,
+        };
+
+        // The range immediately before `index` may still touch `range` (end
+        // exactly at `range.start`) even though `range.start` didn't fall
+        // inside it.
+        if index > 0 && self.0[index - 1].end >= range.start {
+            index -= 1;
         }
-        self.0.push(range);
-        return;
+
+        if index == self.0.len() || self.0[index].start > range.end {
+            self.0.insert(index, range);
+            return;
+        }
+
+        self.0[index].start = self.0[index].start.min(range.start);
+        self.0[index].end = self.0[index].end.max(range.end);
+
+        // Absorb any following ranges that now touch or overlap.
+        let mut next = index + 1;
+        while next < self.0.len() && self.0[next].start <= self.0[index].end {
+            self.0[index].end = self.0[index].end.max(self.0[next].end);
+            next += 1;
+        }
+        self.0.drain(index + 1..next);
     }
 
     /// Inserts range into the range set using a start and end number
-    /// 
+    ///
     /// example:
-    /// 
+    ///
     /// let mut set = RangeSet::new();
     /// let start = 1;
     /// let end = 5;
     /// set.insert_num(1, 5);
-    pub fn insert_num(&mut self, start: u64, end: u64) {
-        let range = std::ops::Range {
-            start: start,
-            end: end,
-        };
-        if range.is_empty() {
-            return;
-        }
-        let mut index = 0;
-        while index != self.0.len() {
-            let current = &mut self.0[index];
-            if current.start > range.end {
-                self.0.insert(index, range);
-                return;
-            } else if current.start > range.start {
-                current.start = range.start;
-                return;
-            }
-            if range.end <= current.end {
-                return;
-            } else if range.start <= current.end {
-                current.end = range.end;
-                while index != self.0.len() - 1 {
-                    let curr = self.0[index].clone();
-                    let next = self.0[index + 1].clone();
-                    if curr.end >= next.start {
-                        self.0[index].end = next.end.max(curr.end);
-                        self.0.remove(index + 1);
-                    } else {
-                        break;
-                    }
-                }
-                return;
-            }
-            index += 1;
-        }
-        self.0.push(range);
-        return;
+    pub fn insert_num(&mut self, start: T, end: T) {
+        self.insert(start..end);
     }
 
     /// Removes a range from the range set
     /// uses use std::ops::Range
-    pub fn remove(&mut self, range: Range<u64>) {
+    pub fn remove(&mut self, range: Range<T>) {
         if range.is_empty() {
             return;
         }
-        let mut index = 0;
+
+        let mut index = match self.search(range.start) {
+            Ok(idx) => idx,
+            Err(idx) => idx,
+        };
+
         while index != self.0.len() && range.start != range.end {
             let current = self.0[index].clone();
 
@@ -180,6 +161,361 @@ impl RangeSet {
                 index += 2;
             }
         }
-        return;
+    }
+
+    /// Every value covered by `self` or `other`.
+    pub fn union(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut out = self.clone();
+        out.union_with(other);
+        out
+    }
+
+    /// Union `other` into `self` in place, avoiding an extra allocation
+    /// when the caller already owns `self`.
+    pub fn union_with(&mut self, other: &RangeSet<T>) {
+        let mut merged: TinyVec<[Range<T>; INLINE_CAPACITY]> = TinyVec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.0.len() || j < other.0.len() {
+            let next = if j >= other.0.len() || (i < self.0.len() && self.0[i].start <= other.0[j].start)
+            {
+                let r = self.0[i].clone();
+                i += 1;
+                r
+            } else {
+                let r = other.0[j].clone();
+                j += 1;
+                r
+            };
+            match merged.last_mut() {
+                Some(last) if next.start <= last.end => last.end = last.end.max(next.end),
+                _ => merged.push(next),
+            }
+        }
+        self.0 = merged;
+    }
+
+    /// Every value covered by both `self` and `other`.
+    pub fn intersection(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut out = self.clone();
+        out.intersect_with(other);
+        out
+    }
+
+    /// Intersect `self` with `other` in place, avoiding an extra
+    /// allocation when the caller already owns `self`.
+    pub fn intersect_with(&mut self, other: &RangeSet<T>) {
+        let mut merged: TinyVec<[Range<T>; INLINE_CAPACITY]> = TinyVec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.0.len() && j < other.0.len() {
+            let a = &self.0[i];
+            let b = &other.0[j];
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                merged.push(start..end);
+            }
+            if a.end <= b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        self.0 = merged;
+    }
+
+    /// Every value covered by `self` but not by `other`.
+    pub fn difference(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut out = RangeSet::new();
+        let mut j = 0;
+        for a in self.0.iter() {
+            let mut cursor = a.start;
+            while j < other.0.len() && other.0[j].end <= cursor {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.0.len() && other.0[k].start < a.end {
+                let b = &other.0[k];
+                if b.start > cursor {
+                    out.0.push(cursor..b.start);
+                }
+                cursor = cursor.max(b.end);
+                k += 1;
+            }
+            if cursor < a.end {
+                out.0.push(cursor..a.end);
+            }
+        }
+        out
+    }
+
+    /// Every value covered by exactly one of `self` and `other`.
+    pub fn symmetric_difference(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        self.union(other).difference(&self.intersection(other))
+    }
+
+    /// Yield the maximal sub-ranges of `bound` not covered by any stored
+    /// range, in ascending order — e.g. the spans a download scheduler
+    /// still needs to fetch within a requested window.
+    ///
+    /// An empty or inverted `bound` yields nothing; an empty set yields
+    /// `bound` itself.
+    pub fn gaps(&self, bound: Range<T>) -> impl Iterator<Item = Range<T>> + '_ {
+        let mut index = if bound.is_empty() {
+            self.0.len()
+        } else {
+            match self.search(bound.start) {
+                Ok(idx) => idx,
+                Err(idx) => idx,
+            }
+        };
+        let mut cursor = bound.start;
+
+        std::iter::from_fn(move || {
+            while index < self.0.len() {
+                let current = self.0[index].clone();
+                if current.start >= bound.end {
+                    break;
+                }
+                index += 1;
+                if current.start > cursor {
+                    let gap = cursor..current.start;
+                    cursor = current.end.max(cursor);
+                    return Some(gap);
+                }
+                cursor = current.end.max(cursor);
+            }
+            if cursor < bound.end {
+                let gap = cursor..bound.end;
+                cursor = bound.end;
+                return Some(gap);
+            }
+            None
+        })
+    }
+}
+
+impl RangeSet<u64> {
+    /// Serialize the range set as a little-endian run-length encoding: a
+    /// `u64` count of ranges followed by that many `(start, end)` `u64`
+    /// pairs. Gives callers a stable, allocation-light format for
+    /// persisting sync/progress state to disk or sending it over the
+    /// wire, independent of Rust's in-memory layout.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_u64::<LittleEndian>(self.0.len() as u64)?;
+        for range in self.0.iter() {
+            w.write_u64::<LittleEndian>(range.start)?;
+            w.write_u64::<LittleEndian>(range.end)?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a range set written by [`RangeSet::write_to`].
+    ///
+    /// The pairs are streamed straight into the inner vec without
+    /// re-running `insert`, since a well-formed payload is already
+    /// sorted, disjoint, and merged. Monotonicity (`start < end` and
+    /// each `start > previous end`) is validated so a corrupt buffer
+    /// can't produce touching-but-unmerged ranges, which would violate
+    /// that invariant.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<RangeSet<u64>> {
+        let count = r.read_u64::<LittleEndian>()?;
+        let mut set = RangeSet::new();
+        let mut previous_end = None;
+        for _ in 0..count {
+            let start = r.read_u64::<LittleEndian>()?;
+            let end = r.read_u64::<LittleEndian>()?;
+            if start >= end {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "range start must be less than end",
+                ));
+            }
+            if previous_end.is_some_and(|prev| start <= prev) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "ranges must be sorted, disjoint, and non-touching",
+                ));
+            }
+            previous_end = Some(end);
+            set.0.push(start..end);
+        }
+        Ok(set)
+    }
+
+    /// Flatten the stored ranges into their individual members, in
+    /// ascending order.
+    pub fn elements(&self) -> impl Iterator<Item = u64> + '_ {
+        self.0.iter().flat_map(|r| r.clone())
+    }
+
+    /// The total number of covered values (sum of `end - start` over all
+    /// ranges), distinct from [`RangeSet::len`] which counts ranges.
+    pub fn count(&self) -> u64 {
+        self.0.iter().map(|r| r.end - r.start).sum()
+    }
+}
+
+impl FromIterator<Range<u64>> for RangeSet<u64> {
+    fn from_iter<I: IntoIterator<Item = Range<u64>>>(iter: I) -> Self {
+        let mut set = RangeSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Range<u64>> for RangeSet<u64> {
+    fn extend<I: IntoIterator<Item = Range<u64>>>(&mut self, iter: I) {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+impl IntoIterator for RangeSet<u64> {
+    type Item = Range<u64>;
+    type IntoIter = tinyvec::TinyVecIterator<[Range<u64>; INLINE_CAPACITY]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn set(ranges: &[Range<u64>]) -> RangeSet<u64> {
+        ranges.iter().cloned().collect()
+    }
+
+    fn ranges(set: &RangeSet<u64>) -> Vec<Range<u64>> {
+        set.iter().collect()
+    }
+
+    #[test]
+    fn insert_merges_touching_ranges() {
+        let mut s = set(&[0..5, 10..15]);
+        s.insert(5..10);
+        assert_eq!(ranges(&s), vec![0..15]);
+    }
+
+    #[test]
+    fn insert_merges_overlapping_ranges() {
+        let mut s = set(&[0..5, 20..25]);
+        s.insert(3..22);
+        assert_eq!(ranges(&s), vec![0..25]);
+    }
+
+    #[test]
+    fn remove_splits_range() {
+        let mut s = set(&[0..10]);
+        s.remove(3..6);
+        assert_eq!(ranges(&s), vec![0..3, 6..10]);
+    }
+
+    #[test]
+    fn remove_shrinks_from_either_edge() {
+        let mut s = set(&[0..10]);
+        s.remove(0..3);
+        assert_eq!(ranges(&s), vec![3..10]);
+        s.remove(8..10);
+        assert_eq!(ranges(&s), vec![3..8]);
+    }
+
+    #[test]
+    fn union_coalesces_overlaps() {
+        let a = set(&[0..5, 10..15]);
+        let b = set(&[4..12]);
+        assert_eq!(ranges(&a.union(&b)), vec![0..15]);
+    }
+
+    #[test]
+    fn intersection_keeps_overlap_only() {
+        let a = set(&[0..10, 20..30]);
+        let b = set(&[5..25]);
+        assert_eq!(ranges(&a.intersection(&b)), vec![5..10, 20..25]);
+    }
+
+    #[test]
+    fn difference_splits_subtracted_range() {
+        let a = set(&[0..10]);
+        let b = set(&[3..6]);
+        assert_eq!(ranges(&a.difference(&b)), vec![0..3, 6..10]);
+    }
+
+    #[test]
+    fn symmetric_difference_excludes_overlap() {
+        let a = set(&[0..10]);
+        let b = set(&[5..15]);
+        assert_eq!(ranges(&a.symmetric_difference(&b)), vec![0..5, 10..15]);
+    }
+
+    #[test]
+    fn gaps_on_empty_set_yields_whole_bound() {
+        let s: RangeSet<u64> = RangeSet::new();
+        assert_eq!(s.gaps(0..10).collect::<Vec<_>>(), vec![0..10]);
+    }
+
+    #[test]
+    fn gaps_on_empty_or_inverted_bound_yields_nothing() {
+        let s = set(&[0..10]);
+        assert_eq!(s.gaps(5..5).collect::<Vec<_>>(), Vec::<Range<u64>>::new());
+        let inverted = Range { start: 8, end: 2 };
+        assert_eq!(s.gaps(inverted).collect::<Vec<_>>(), Vec::<Range<u64>>::new());
+    }
+
+    #[test]
+    fn gaps_skips_ranges_outside_bound_and_finds_interior_holes() {
+        let s = set(&[0..2, 5..8, 20..25]);
+        assert_eq!(s.gaps(3..12).collect::<Vec<_>>(), vec![3..5, 8..12]);
+    }
+
+    #[test]
+    fn write_then_read_round_trips() {
+        let s = set(&[0..5, 10..20]);
+        let mut buf = Vec::new();
+        s.write_to(&mut buf).unwrap();
+        let read = RangeSet::read_from(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(ranges(&read), ranges(&s));
+    }
+
+    #[test]
+    fn read_from_rejects_empty_or_inverted_range() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&1u64.to_le_bytes());
+        buf.extend_from_slice(&5u64.to_le_bytes());
+        buf.extend_from_slice(&5u64.to_le_bytes());
+        let err = RangeSet::read_from(&mut Cursor::new(buf)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&1u64.to_le_bytes());
+        buf.extend_from_slice(&8u64.to_le_bytes());
+        buf.extend_from_slice(&2u64.to_le_bytes());
+        let err = RangeSet::read_from(&mut Cursor::new(buf)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_from_rejects_touching_or_out_of_order_ranges() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&2u64.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        buf.extend_from_slice(&5u64.to_le_bytes());
+        buf.extend_from_slice(&5u64.to_le_bytes());
+        buf.extend_from_slice(&10u64.to_le_bytes());
+        let err = RangeSet::read_from(&mut Cursor::new(buf)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&2u64.to_le_bytes());
+        buf.extend_from_slice(&10u64.to_le_bytes());
+        buf.extend_from_slice(&15u64.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        buf.extend_from_slice(&5u64.to_le_bytes());
+        let err = RangeSet::read_from(&mut Cursor::new(buf)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 }